@@ -3,7 +3,7 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("Input game graph is unbeatable. Change the input data so that the game can be completed.\nThe source scc nodes are: {0:?}")]
+    #[error("Input game graph is unbeatable. Change the input data so that the game can be completed.\nThe nodes unreachable from the start are: {0:?}")]
     GameUnbeatable(Vec<NodeID>),
 }
 
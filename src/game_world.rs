@@ -1,11 +1,17 @@
 use crate::{
     error::Result,
-    graph::{GameGraph, Graph},
+    graph::{GameGraph, Graph, Requirement},
+    Item, NodeID,
 };
 use linked_hash_set::LinkedHashSet;
 use petgraph::graph::EdgeIndex;
 use rand::{rngs::StdRng, seq::IteratorRandom, Rng};
-use std::{fmt::Debug, hash::Hash};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
 
 trait Swappable {
     fn swap<G: Graph>(&self, other: &Self, graph: &mut G) -> EdgePair<Self>
@@ -13,7 +19,7 @@ trait Swappable {
         Self: Sized;
 }
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct OneWay {
     idx: EdgeIndex,
 }
@@ -28,7 +34,7 @@ impl OneWay {
     }
 }
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct TwoWay {
     idx1: EdgeIndex,
     idx2: EdgeIndex,
@@ -73,15 +79,15 @@ fn swap_edges<G: Graph>(
     let (edge1a, edge1b) = graph.edge_endpoints(edge1).unwrap();
     let (edge2a, edge2b) = graph.edge_endpoints(edge2).unwrap();
 
-    graph
+    let req1 = graph
         .remove_edge(edge1)
         .unwrap_or_else(|| panic!("Failed to remove edge ({:?}, {:?})", edge1a, edge1b));
-    graph
+    let req2 = graph
         .remove_edge(edge2)
         .unwrap_or_else(|| panic!("Failed to remove edge ({:?}, {:?})", edge2a, edge2b));
 
-    let new_edge_id1 = graph.add_edge(edge1a, edge2b);
-    let new_edge_id2 = graph.add_edge(edge2a, edge1b);
+    let new_edge_id1 = graph.add_edge(edge1a, edge2b, req1);
+    let new_edge_id2 = graph.add_edge(edge2a, edge1b, req2);
     (new_edge_id1, new_edge_id2)
 }
 
@@ -89,6 +95,139 @@ pub struct GameWorld {
     pub graph: GameGraph,
     pub swappable_one_ways: LinkedHashSet<OneWay>,
     pub swappable_two_ways: LinkedHashSet<TwoWay>,
+    pub start: NodeID,
+    pub goal: NodeID,
+}
+
+impl GameWorld {
+    pub fn to_dot(&self) -> String {
+        let one_way_edges = self
+            .swappable_one_ways
+            .iter()
+            .map(|one_way| one_way.get_idx())
+            .collect();
+        let two_way_edges = self
+            .swappable_two_ways
+            .iter()
+            .flat_map(|two_way| [two_way.get_idx1(), two_way.get_idx2()])
+            .collect();
+
+        self.graph.to_dot(&one_way_edges, &two_way_edges)
+    }
+}
+
+// `OneWay`/`TwoWay` only derive `Serialize`/`Deserialize` over their raw
+// `EdgeIndex`es, which are meaningless on their own: `try_swap_edges` relies
+// on them pointing at edges in *this* `GameWorld`'s graph. So `GameWorld`
+// re-expresses swappable-set membership as `NodeID` pairs and resolves them
+// back to `EdgeIndex`es against the freshly rebuilt graph, rather than
+// trusting the derived index-based encoding to still line up after a
+// round-trip. A `(NodeID, NodeID)` pair alone is ambiguous when the graph
+// holds parallel edges between those nodes (swaps routinely produce these),
+// so each `EdgeRef` also carries its `edge_occurrence` to pick out the right
+// one via `find_nth_edge`.
+type EdgeRef = (NodeID, NodeID, usize);
+
+#[derive(Serialize, Deserialize)]
+struct SerializedGameWorld {
+    edges: Vec<(NodeID, NodeID, Requirement)>,
+    swappable_one_ways: Vec<EdgeRef>,
+    swappable_two_ways: Vec<(EdgeRef, EdgeRef)>,
+    start: NodeID,
+    goal: NodeID,
+}
+
+impl Serialize for GameWorld {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let edges = self
+            .graph
+            .edge_indices()
+            .map(|e| {
+                let (a, b) = self.graph.edge_endpoints(e).unwrap();
+                (a, b, self.graph.requirement(e).unwrap())
+            })
+            .collect();
+
+        let edge_occurrence = |e: EdgeIndex| -> EdgeRef {
+            let (a, b) = self.graph.edge_endpoints(e).unwrap();
+            (a, b, self.graph.edge_occurrence(e).unwrap())
+        };
+
+        let swappable_one_ways = self
+            .swappable_one_ways
+            .iter()
+            .map(|one_way| edge_occurrence(one_way.get_idx()))
+            .collect();
+
+        let swappable_two_ways = self
+            .swappable_two_ways
+            .iter()
+            .map(|two_way| {
+                (
+                    edge_occurrence(two_way.get_idx1()),
+                    edge_occurrence(two_way.get_idx2()),
+                )
+            })
+            .collect();
+
+        SerializedGameWorld {
+            edges,
+            swappable_one_ways,
+            swappable_two_ways,
+            start: self.start,
+            goal: self.goal,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameWorld {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let serialized = SerializedGameWorld::deserialize(deserializer)?;
+        let graph = GameGraph::from_edges(serialized.edges);
+
+        let find_edge = |a: NodeID,
+                         b: NodeID,
+                         occurrence: usize|
+         -> std::result::Result<EdgeIndex, D::Error> {
+            graph.find_nth_edge(a, b, occurrence).ok_or_else(|| {
+                de::Error::custom(format!(
+                    "no edge ({:?}, {:?}) occurrence {} in graph",
+                    a, b, occurrence
+                ))
+            })
+        };
+
+        let swappable_one_ways = serialized
+            .swappable_one_ways
+            .into_iter()
+            .map(|(a, b, occurrence)| find_edge(a, b, occurrence).map(OneWay::new))
+            .collect::<std::result::Result<LinkedHashSet<OneWay>, D::Error>>()?;
+
+        let swappable_two_ways = serialized
+            .swappable_two_ways
+            .into_iter()
+            .map(|((a1, b1, o1), (a2, b2, o2))| {
+                let idx1 = find_edge(a1, b1, o1)?;
+                let idx2 = find_edge(a2, b2, o2)?;
+                Ok(TwoWay::new(idx1, idx2))
+            })
+            .collect::<std::result::Result<LinkedHashSet<TwoWay>, D::Error>>()?;
+
+        Ok(GameWorld {
+            graph,
+            swappable_one_ways,
+            swappable_two_ways,
+            start: serialized.start,
+            goal: serialized.goal,
+        })
+    }
 }
 
 struct EdgePair<T: Swappable>(T, T);
@@ -105,15 +244,25 @@ where
     }
 }
 
-fn try_swap_edges<T, G>(graph: &mut G, swap_edges: &mut LinkedHashSet<T>, rng: &mut StdRng)
-where
+fn try_swap_edges<T, G>(
+    graph: &mut G,
+    swap_edges: &mut LinkedHashSet<T>,
+    rng: &mut StdRng,
+    start: NodeID,
+    goal: NodeID,
+    starting_items: &HashSet<Item>,
+    item_locations: &HashMap<NodeID, HashSet<Item>>,
+) where
     G: Graph,
     T: Debug + Copy + Clone + Hash + Eq + Swappable,
 {
     if let Some(EdgePair(edge1, edge2)) = pick_random_edges(swap_edges, rng) {
         let EdgePair(new_edge1, new_edge2) = edge1.swap(&edge2, graph);
 
-        if graph.game_beatable().is_err() {
+        if graph
+            .game_beatable(start, goal, starting_items, item_locations)
+            .is_err()
+        {
             new_edge1.swap(&new_edge2, graph);
         } else {
             if !swap_edges.remove(&edge1) {
@@ -139,8 +288,15 @@ pub fn build_game(
     mut game_world: GameWorld,
     rng: &mut StdRng,
     iterations: usize,
+    starting_items: &HashSet<Item>,
+    item_locations: &HashMap<NodeID, HashSet<Item>>,
 ) -> Result<GameWorld> {
-    let _ = game_world.graph.game_beatable()?;
+    let start = game_world.start;
+    let goal = game_world.goal;
+
+    let _ = game_world
+        .graph
+        .game_beatable(start, goal, starting_items, item_locations)?;
 
     for _ in 0..iterations {
         if rng.gen::<bool>() {
@@ -148,12 +304,20 @@ pub fn build_game(
                 &mut game_world.graph,
                 &mut game_world.swappable_one_ways,
                 rng,
+                start,
+                goal,
+                starting_items,
+                item_locations,
             );
         } else {
             try_swap_edges(
                 &mut game_world.graph,
                 &mut game_world.swappable_two_ways,
                 rng,
+                start,
+                goal,
+                starting_items,
+                item_locations,
             );
         }
     }
@@ -166,25 +330,25 @@ mod tests {
     use rand::SeedableRng;
 
     fn graph() -> GameGraph {
-        GameGraph::from_edges(&[
-            (0, 1),
-            (1, 2),
-            (2, 1),
-            (2, 3),
-            (3, 4),
-            (4, 3),
-            (4, 5),
-            (5, 4),
-            (3, 5),
-            (5, 3),
-            (4, 6),
-            (6, 8),
-            (8, 6),
-            (5, 7),
-            (7, 9),
-            (9, 7),
-            (8, 10),
-            (9, 10),
+        GameGraph::from_edges(vec![
+            (0, 1, Requirement::none()),
+            (1, 2, Requirement::none()),
+            (2, 1, Requirement::none()),
+            (2, 3, Requirement::none()),
+            (3, 4, Requirement::none()),
+            (4, 3, Requirement::none()),
+            (4, 5, Requirement::none()),
+            (5, 4, Requirement::none()),
+            (3, 5, Requirement::none()),
+            (5, 3, Requirement::none()),
+            (4, 6, Requirement::none()),
+            (6, 8, Requirement::none()),
+            (8, 6, Requirement::none()),
+            (5, 7, Requirement::none()),
+            (7, 9, Requirement::none()),
+            (9, 7, Requirement::none()),
+            (8, 10, Requirement::none()),
+            (9, 10, Requirement::none()),
         ])
     }
 
@@ -192,9 +356,9 @@ mod tests {
     fn test_swap() {
         let mut graph = graph();
 
-        let edge1 = OneWay::new(graph.add_edge(4, 6));
+        let edge1 = OneWay::new(graph.add_edge(4, 6, Requirement::none()));
 
-        let edge2 = OneWay::new(graph.add_edge(8, 10));
+        let edge2 = OneWay::new(graph.add_edge(8, 10, Requirement::none()));
 
         let (idx1, idx2) = swap_edges(edge1.idx, edge2.idx, &mut graph);
         assert!(graph.edge_endpoints(idx1).unwrap() == (4, 10));
@@ -205,8 +369,14 @@ mod tests {
     fn test_swap_two_ways() {
         let mut graph = graph();
 
-        let edge1 = TwoWay::new(graph.add_edge(4, 6), graph.add_edge(6, 4));
-        let edge2 = TwoWay::new(graph.add_edge(8, 10), graph.add_edge(10, 8));
+        let edge1 = TwoWay::new(
+            graph.add_edge(4, 6, Requirement::none()),
+            graph.add_edge(6, 4, Requirement::none()),
+        );
+        let edge2 = TwoWay::new(
+            graph.add_edge(8, 10, Requirement::none()),
+            graph.add_edge(10, 8, Requirement::none()),
+        );
 
         let (idx1, idx2) = swap_edges(edge1.idx1, edge2.idx1, &mut graph);
         let (idx3, idx4) = swap_edges(edge1.idx2, edge2.idx2, &mut graph);
@@ -221,9 +391,9 @@ mod tests {
     fn test_swap_same_endpoint() {
         let mut graph = graph();
 
-        let edge1 = OneWay::new(graph.add_edge(9, 10));
+        let edge1 = OneWay::new(graph.add_edge(9, 10, Requirement::none()));
 
-        let edge2 = OneWay::new(graph.add_edge(8, 10));
+        let edge2 = OneWay::new(graph.add_edge(8, 10, Requirement::none()));
 
         let (idx1, idx2) = swap_edges(edge1.idx, edge2.idx, &mut graph);
 
@@ -235,9 +405,9 @@ mod tests {
     fn test_swap_twice() {
         let mut graph = graph();
 
-        let edge1 = OneWay::new(graph.add_edge(4, 6));
+        let edge1 = OneWay::new(graph.add_edge(4, 6, Requirement::none()));
 
-        let edge2 = OneWay::new(graph.add_edge(8, 10));
+        let edge2 = OneWay::new(graph.add_edge(8, 10, Requirement::none()));
 
         let EdgePair(new_edge1, new_edge2) = edge1.swap(&edge2, &mut graph);
         let EdgePair(final_edge1, final_edge2) = new_edge1.swap(&new_edge2, &mut graph);
@@ -250,8 +420,14 @@ mod tests {
     fn test_swap_twice_two_ways() {
         let mut graph = graph();
 
-        let edge1 = TwoWay::new(graph.add_edge(4, 6), graph.add_edge(6, 4));
-        let edge2 = TwoWay::new(graph.add_edge(8, 10), graph.add_edge(10, 8));
+        let edge1 = TwoWay::new(
+            graph.add_edge(4, 6, Requirement::none()),
+            graph.add_edge(6, 4, Requirement::none()),
+        );
+        let edge2 = TwoWay::new(
+            graph.add_edge(8, 10, Requirement::none()),
+            graph.add_edge(10, 8, Requirement::none()),
+        );
 
         let EdgePair(new_edge1, new_edge2) = edge1.swap(&edge2, &mut graph);
         let EdgePair(final_edge1, final_edge2) = new_edge1.swap(&new_edge2, &mut graph);
@@ -266,32 +442,51 @@ mod tests {
     fn test_beatable() {
         let graph = graph();
 
-        assert!(graph.game_beatable().is_ok());
+        assert!(graph
+            .game_beatable(0, 10, &HashSet::new(), &HashMap::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_path_exists() {
+        let graph = graph();
+
+        assert!(graph.path_exists(0, 10));
+        assert!(!graph.path_exists(10, 0));
+    }
+
+    #[test]
+    fn test_reachable_nodes() {
+        let graph = graph();
+
+        let reachable = graph.reachable_nodes(8);
+        assert!(reachable.contains(&10));
+        assert!(!reachable.contains(&0));
     }
 
     #[test]
     fn test_shuffle() {
-        let mut graph = GameGraph::from_edges(&[
-            (1, 2),
-            (2, 1),
-            (3, 4),
-            (4, 3),
-            (4, 5),
-            (5, 4),
-            (3, 5),
-            (5, 3),
-            (6, 8),
-            (8, 6),
-            (7, 9),
-            (9, 7),
+        let mut graph = GameGraph::from_edges(vec![
+            (1, 2, Requirement::none()),
+            (2, 1, Requirement::none()),
+            (3, 4, Requirement::none()),
+            (4, 3, Requirement::none()),
+            (4, 5, Requirement::none()),
+            (5, 4, Requirement::none()),
+            (3, 5, Requirement::none()),
+            (5, 3, Requirement::none()),
+            (6, 8, Requirement::none()),
+            (8, 6, Requirement::none()),
+            (7, 9, Requirement::none()),
+            (9, 7, Requirement::none()),
         ]);
 
-        let ow1 = OneWay::new(graph.add_edge(0, 1));
-        let ow2 = OneWay::new(graph.add_edge(2, 3));
-        let ow3 = OneWay::new(graph.add_edge(4, 6));
-        let ow4 = OneWay::new(graph.add_edge(5, 7));
-        let ow5 = OneWay::new(graph.add_edge(8, 10));
-        let ow6 = OneWay::new(graph.add_edge(9, 10));
+        let ow1 = OneWay::new(graph.add_edge(0, 1, Requirement::none()));
+        let ow2 = OneWay::new(graph.add_edge(2, 3, Requirement::none()));
+        let ow3 = OneWay::new(graph.add_edge(4, 6, Requirement::none()));
+        let ow4 = OneWay::new(graph.add_edge(5, 7, Requirement::none()));
+        let ow5 = OneWay::new(graph.add_edge(8, 10, Requirement::none()));
+        let ow6 = OneWay::new(graph.add_edge(9, 10, Requirement::none()));
 
         let mut ow_hashset = LinkedHashSet::new();
         ow_hashset.insert(ow1);
@@ -307,11 +502,118 @@ mod tests {
             graph,
             swappable_one_ways: ow_hashset,
             swappable_two_ways: LinkedHashSet::new(),
+            start: 0,
+            goal: 10,
         };
 
-        let game = build_game(game_world, &mut rng, 500).unwrap();
+        let game = build_game(game_world, &mut rng, 500, &HashSet::new(), &HashMap::new())
+            .unwrap();
 
         assert_eq!(game.graph.edge_count(), 18);
-        assert!(game.graph.game_beatable().is_ok());
+        assert!(game
+            .graph
+            .game_beatable(0, 10, &HashSet::new(), &HashMap::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut graph = graph();
+        // Parallel to the existing (8, 10) edge, so the round trip has to
+        // disambiguate which one is swappable.
+        let ow1 = OneWay::new(graph.add_edge(8, 10, Requirement::needing(vec![42])));
+        let tw1 = TwoWay::new(
+            graph.add_edge(4, 6, Requirement::none()),
+            graph.add_edge(6, 4, Requirement::none()),
+        );
+
+        let mut swappable_one_ways = LinkedHashSet::new();
+        swappable_one_ways.insert(ow1);
+        let mut swappable_two_ways = LinkedHashSet::new();
+        swappable_two_ways.insert(tw1);
+
+        let game_world = GameWorld {
+            graph,
+            swappable_one_ways,
+            swappable_two_ways,
+            start: 0,
+            goal: 10,
+        };
+
+        let json = serde_json::to_string(&game_world).unwrap();
+        let restored: GameWorld = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.start, game_world.start);
+        assert_eq!(restored.goal, game_world.goal);
+
+        let edges = |world: &GameWorld| -> Vec<(NodeID, NodeID, Requirement)> {
+            let mut edges: Vec<_> = world
+                .graph
+                .edge_indices()
+                .map(|e| {
+                    let (a, b) = world.graph.edge_endpoints(e).unwrap();
+                    (a, b, world.graph.requirement(e).unwrap())
+                })
+                .collect();
+            edges.sort_by_key(|(a, b, _)| (*a, *b));
+            edges
+        };
+        assert_eq!(edges(&game_world), edges(&restored));
+
+        let one_way_endpoints = |world: &GameWorld| -> Vec<(NodeID, NodeID, Requirement)> {
+            world
+                .swappable_one_ways
+                .iter()
+                .map(|one_way| {
+                    let (a, b) = world.graph.edge_endpoints(one_way.get_idx()).unwrap();
+                    (a, b, world.graph.requirement(one_way.get_idx()).unwrap())
+                })
+                .collect()
+        };
+        assert_eq!(one_way_endpoints(&game_world), one_way_endpoints(&restored));
+
+        let two_way_endpoints = |world: &GameWorld| -> Vec<(NodeID, NodeID, NodeID, NodeID)> {
+            world
+                .swappable_two_ways
+                .iter()
+                .map(|two_way| {
+                    let (a1, b1) = world.graph.edge_endpoints(two_way.get_idx1()).unwrap();
+                    let (a2, b2) = world.graph.edge_endpoints(two_way.get_idx2()).unwrap();
+                    (a1, b1, a2, b2)
+                })
+                .collect()
+        };
+        assert_eq!(two_way_endpoints(&game_world), two_way_endpoints(&restored));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = graph();
+        let one_way = OneWay::new(graph.add_edge(8, 10, Requirement::none()));
+        let two_way = TwoWay::new(
+            graph.add_edge(4, 6, Requirement::none()),
+            graph.add_edge(6, 4, Requirement::none()),
+        );
+
+        let mut swappable_one_ways = LinkedHashSet::new();
+        swappable_one_ways.insert(one_way);
+        let mut swappable_two_ways = LinkedHashSet::new();
+        swappable_two_ways.insert(two_way);
+
+        let game_world = GameWorld {
+            graph,
+            swappable_one_ways,
+            swappable_two_ways,
+            start: 0,
+            goal: 10,
+        };
+
+        let dot = game_world.to_dot();
+
+        for node in 0..=10 {
+            assert!(dot.contains(&format!("label=\"{}\"", node)));
+        }
+        assert!(dot.contains("color=red"));
+        assert!(dot.contains("color=blue"));
     }
 }
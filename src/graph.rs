@@ -1,42 +1,71 @@
-use crate::{error::Error, NodeID};
+use crate::{error::Error, Item, NodeID};
 use bimap::BiMap;
 pub use petgraph::{
-    algo::condensation,
     graph::{EdgeIndex, NodeIndex},
     stable_graph::{EdgeIndices, StableDiGraph},
-    Direction, IntoWeightedEdge,
+    IntoWeightedEdge,
 };
+use petgraph::algo::dominators::{self, Dominators as PetgraphDominators};
+use petgraph::dot::{Config, Dot};
+use petgraph::visit::{Bfs, EdgeRef};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requirement(HashSet<Item>);
+
+impl Requirement {
+    pub fn none() -> Self {
+        Requirement(HashSet::new())
+    }
+
+    pub fn needing(items: impl IntoIterator<Item = Item>) -> Self {
+        Requirement(items.into_iter().collect())
+    }
+
+    pub fn is_satisfied_by(&self, held_items: &HashSet<Item>) -> bool {
+        self.0.iter().all(|item| held_items.contains(item))
+    }
+}
 
 pub trait Graph {
     fn from_edges<I>(iterable: I) -> Self
     where
         I: IntoIterator,
-        I::Item: IntoWeightedEdge<(), NodeId = NodeID>;
+        I::Item: IntoWeightedEdge<Requirement, NodeId = NodeID>;
     fn edge_count(&self) -> usize;
     fn edge_endpoints(&self, e: EdgeIndex) -> Option<(NodeID, NodeID)>;
-    fn edge_indices(&self) -> EdgeIndices<()>;
-    fn add_edge(&mut self, node1: NodeID, node2: NodeID) -> EdgeIndex;
-    fn remove_edge(&mut self, e: EdgeIndex) -> Option<()>;
-    fn game_beatable(&self) -> Result<(), Error>;
+    fn requirement(&self, e: EdgeIndex) -> Option<Requirement>;
+    fn edge_indices(&self) -> EdgeIndices<Requirement>;
+    fn add_edge(&mut self, node1: NodeID, node2: NodeID, requirement: Requirement) -> EdgeIndex;
+    fn remove_edge(&mut self, e: EdgeIndex) -> Option<Requirement>;
+    fn game_beatable(
+        &self,
+        start: NodeID,
+        goal: NodeID,
+        starting_items: &HashSet<Item>,
+        item_locations: &HashMap<NodeID, HashSet<Item>>,
+    ) -> Result<(), Error>;
 }
 
 pub struct GameGraph {
-    graph: StableDiGraph<NodeID, ()>,
+    graph: StableDiGraph<NodeID, Requirement>,
     node_map: BiMap<NodeID, NodeIndex>,
 }
 
 fn insert_edge(
-    graph: &mut StableDiGraph<NodeID, ()>,
+    graph: &mut StableDiGraph<NodeID, Requirement>,
     node_map: &mut BiMap<NodeID, NodeIndex>,
     a: NodeID,
     b: NodeID,
+    requirement: Requirement,
 ) -> EdgeIndex {
     match node_map.get_by_left(&a) {
-        Some(a_idx) => ensure_end_exists_and_insert_edge(*a_idx, b, node_map, graph),
+        Some(a_idx) => ensure_end_exists_and_insert_edge(*a_idx, b, requirement, node_map, graph),
         None => {
             let a_index = graph.add_node(a);
             node_map.insert(a, a_index);
-            ensure_end_exists_and_insert_edge(a_index, b, node_map, graph)
+            ensure_end_exists_and_insert_edge(a_index, b, requirement, node_map, graph)
         }
     }
 }
@@ -44,15 +73,16 @@ fn insert_edge(
 fn ensure_end_exists_and_insert_edge(
     a_idx: NodeIndex,
     b: NodeID,
+    requirement: Requirement,
     node_map: &mut BiMap<NodeID, NodeIndex>,
-    graph: &mut StableDiGraph<NodeID, ()>,
+    graph: &mut StableDiGraph<NodeID, Requirement>,
 ) -> EdgeIndex {
     match node_map.get_by_left(&b) {
-        Some(b_idx) => graph.add_edge(a_idx, *b_idx, ()),
+        Some(b_idx) => graph.add_edge(a_idx, *b_idx, requirement),
         None => {
             let b_index = graph.add_node(b);
             node_map.insert(b, b_index);
-            graph.add_edge(a_idx, b_index, ())
+            graph.add_edge(a_idx, b_index, requirement)
         }
     }
 }
@@ -61,15 +91,15 @@ impl Graph for GameGraph {
     fn from_edges<I>(iterable: I) -> Self
     where
         I: IntoIterator,
-        I::Item: IntoWeightedEdge<(), NodeId = NodeID>,
+        I::Item: IntoWeightedEdge<Requirement, NodeId = NodeID>,
     {
         let mut graph = StableDiGraph::new();
 
         let mut node_map = BiMap::new();
 
         for i in iterable.into_iter() {
-            let (a, b, _) = i.into_weighted_edge();
-            insert_edge(&mut graph, &mut node_map, a, b);
+            let (a, b, requirement) = i.into_weighted_edge();
+            insert_edge(&mut graph, &mut node_map, a, b, requirement);
         }
 
         GameGraph { graph, node_map }
@@ -87,33 +117,311 @@ impl Graph for GameGraph {
         Some((*id1, *id2))
     }
 
-    fn add_edge(&mut self, a: NodeID, b: NodeID) -> EdgeIndex {
-        insert_edge(&mut self.graph, &mut self.node_map, a, b)
+    fn requirement(&self, e: EdgeIndex) -> Option<Requirement> {
+        self.graph.edge_weight(e).cloned()
+    }
+
+    fn add_edge(&mut self, a: NodeID, b: NodeID, requirement: Requirement) -> EdgeIndex {
+        insert_edge(&mut self.graph, &mut self.node_map, a, b, requirement)
     }
 
-    fn remove_edge(&mut self, e: EdgeIndex) -> Option<()> {
+    fn remove_edge(&mut self, e: EdgeIndex) -> Option<Requirement> {
         self.graph.remove_edge(e)
     }
 
-    fn game_beatable(&self) -> Result<(), Error> {
-        let condensed_graph = condensation(
-            self.graph.map(|_, n| n, |_, e| e).into(),
-            /*make_acyclic*/ true,
-        );
+    fn game_beatable(
+        &self,
+        start: NodeID,
+        goal: NodeID,
+        starting_items: &HashSet<Item>,
+        item_locations: &HashMap<NodeID, HashSet<Item>>,
+    ) -> Result<(), Error> {
+        let reachable_nodes = self.reachable_with_items(start, starting_items, item_locations);
 
-        if condensed_graph.externals(Direction::Incoming).count() == 1 {
+        if reachable_nodes.contains(&goal) {
             Ok(())
         } else {
-            let node_ids = condensed_graph
-                .externals(Direction::Incoming)
-                .flat_map(|idx| condensed_graph.node_weight(idx).unwrap())
-                .map(|id| **id)
+            let unreachable_nodes = self
+                .node_map
+                .left_values()
+                .filter(|node| !reachable_nodes.contains(node))
+                .copied()
                 .collect::<Vec<NodeID>>();
-            Err(Error::GameUnbeatable(node_ids))
+            Err(Error::GameUnbeatable(unreachable_nodes))
         }
     }
 
-    fn edge_indices(&self) -> EdgeIndices<()> {
+    fn edge_indices(&self) -> EdgeIndices<Requirement> {
         self.graph.edge_indices()
     }
 }
+
+impl GameGraph {
+    pub fn find_edge(&self, a: NodeID, b: NodeID) -> Option<EdgeIndex> {
+        let a_idx = self.node_map.get_by_left(&a)?;
+        let b_idx = self.node_map.get_by_left(&b)?;
+        self.graph.find_edge(*a_idx, *b_idx)
+    }
+
+    // `find_edge` only ever returns the first edge between `a` and `b`, which
+    // is ambiguous when the `StableDiGraph` holds parallel edges between the
+    // same pair of nodes (swaps routinely create these). `occurrence` picks
+    // out the nth such edge in `edge_indices` order, matching `edge_occurrence`
+    // below, so parallel edges round-trip to the right `EdgeIndex`.
+    pub fn find_nth_edge(&self, a: NodeID, b: NodeID, occurrence: usize) -> Option<EdgeIndex> {
+        let a_idx = *self.node_map.get_by_left(&a)?;
+        let b_idx = *self.node_map.get_by_left(&b)?;
+        self.graph
+            .edge_indices()
+            .filter(|&e| self.graph.edge_endpoints(e) == Some((a_idx, b_idx)))
+            .nth(occurrence)
+    }
+
+    // The position of `e` among all edges sharing its endpoints, in
+    // `edge_indices` order. Pairs with `find_nth_edge` to disambiguate
+    // parallel edges across a serialize/deserialize round trip.
+    pub fn edge_occurrence(&self, e: EdgeIndex) -> Option<usize> {
+        let endpoints = self.graph.edge_endpoints(e)?;
+        Some(
+            self.graph
+                .edge_indices()
+                .take_while(|&other| other != e)
+                .filter(|&other| self.graph.edge_endpoints(other) == Some(endpoints))
+                .count(),
+        )
+    }
+
+    // Structural dominance over the raw graph topology, ignoring `Requirement`
+    // gating. Useful for finding choke-point regions every path from `start`
+    // must pass through, regardless of which items unlock which edges.
+    pub fn dominators(&self, start: NodeID) -> Option<Dominators<'_>> {
+        let start_idx = *self.node_map.get_by_left(&start)?;
+        Some(Dominators {
+            inner: dominators::simple_fast(&self.graph, start_idx),
+            node_map: &self.node_map,
+        })
+    }
+
+    // Structural reachability over the raw graph topology, ignoring
+    // `Requirement` gating. Unlike `reachable_with_items`, this is what a
+    // node's reachability looks like regardless of held items.
+    pub fn reachable_nodes(&self, start: NodeID) -> HashSet<NodeID> {
+        let start_idx = match self.node_map.get_by_left(&start) {
+            Some(&idx) => idx,
+            None => return HashSet::new(),
+        };
+
+        let mut bfs = Bfs::new(&self.graph, start_idx);
+        let mut reachable_nodes = HashSet::new();
+        while let Some(idx) = bfs.next(&self.graph) {
+            if let Some(&node) = self.node_map.get_by_right(&idx) {
+                reachable_nodes.insert(node);
+            }
+        }
+
+        reachable_nodes
+    }
+
+    pub fn path_exists(&self, start: NodeID, goal: NodeID) -> bool {
+        self.reachable_nodes(start).contains(&goal)
+    }
+
+    // Sphere expansion: start with `starting_items` and `start` reached, then
+    // repeatedly pick up the items granted by every reached node and follow
+    // any edge whose `Requirement` the held items now satisfy, until a full
+    // pass adds neither a new node nor a new item.
+    fn reachable_with_items(
+        &self,
+        start: NodeID,
+        starting_items: &HashSet<Item>,
+        item_locations: &HashMap<NodeID, HashSet<Item>>,
+    ) -> HashSet<NodeID> {
+        let mut reachable_nodes = HashSet::new();
+        reachable_nodes.insert(start);
+        let mut held_items = starting_items.clone();
+
+        loop {
+            let mut sphere_grew = false;
+
+            for node in &reachable_nodes {
+                if let Some(items) = item_locations.get(node) {
+                    for &item in items {
+                        sphere_grew |= held_items.insert(item);
+                    }
+                }
+            }
+
+            let mut newly_reached = Vec::new();
+            for &node in &reachable_nodes {
+                if let Some(&node_idx) = self.node_map.get_by_left(&node) {
+                    for edge in self.graph.edges(node_idx) {
+                        if !edge.weight().is_satisfied_by(&held_items) {
+                            continue;
+                        }
+                        if let Some(&target) = self.node_map.get_by_right(&edge.target()) {
+                            if !reachable_nodes.contains(&target) {
+                                newly_reached.push(target);
+                            }
+                        }
+                    }
+                }
+            }
+
+            for node in newly_reached {
+                sphere_grew |= reachable_nodes.insert(node);
+            }
+
+            if !sphere_grew {
+                break;
+            }
+        }
+
+        reachable_nodes
+    }
+
+    pub fn to_dot(
+        &self,
+        one_way_edges: &HashSet<EdgeIndex>,
+        two_way_edges: &HashSet<EdgeIndex>,
+    ) -> String {
+        format!(
+            "{:?}",
+            Dot::with_attr_getters(
+                &self.graph,
+                &[Config::NodeNoLabel],
+                &|_, edge| edge_style(edge.id(), one_way_edges, two_way_edges),
+                &|_, (_, node)| format!("label=\"{:?}\"", node),
+            )
+        )
+    }
+}
+
+pub struct Dominators<'a> {
+    inner: PetgraphDominators<NodeIndex>,
+    node_map: &'a BiMap<NodeID, NodeIndex>,
+}
+
+impl<'a> Dominators<'a> {
+    pub fn immediate_dominator(&self, node: NodeID) -> Option<NodeID> {
+        let idx = *self.node_map.get_by_left(&node)?;
+        let dom_idx = self.inner.immediate_dominator(idx)?;
+        self.node_map.get_by_right(&dom_idx).copied()
+    }
+
+    // Chain of dominators from `node` up to the start node (inclusive of
+    // both ends). Empty if `node` is unreachable from the start.
+    pub fn dominator_chain(&self, node: NodeID) -> Vec<NodeID> {
+        let idx = match self.node_map.get_by_left(&node) {
+            Some(&idx) => idx,
+            None => return Vec::new(),
+        };
+
+        match self.inner.dominators(idx) {
+            Some(chain) => chain
+                .filter_map(|idx| self.node_map.get_by_right(&idx).copied())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Nodes unreachable from the start have no dominator chain at all
+    // (unlike the start node itself, which dominates itself).
+    pub fn is_reachable(&self, node: NodeID) -> bool {
+        match self.node_map.get_by_left(&node) {
+            Some(&idx) => self.inner.dominators(idx).is_some(),
+            None => false,
+        }
+    }
+}
+
+fn edge_style(
+    id: EdgeIndex,
+    one_way_edges: &HashSet<EdgeIndex>,
+    two_way_edges: &HashSet<EdgeIndex>,
+) -> String {
+    if one_way_edges.contains(&id) {
+        "color=red,style=dashed".to_string()
+    } else if two_way_edges.contains(&id) {
+        "color=blue,style=dashed".to_string()
+    } else {
+        String::new()
+    }
+}
+
+// `StableDiGraph`'s own (de)serialization round-trips internal `NodeIndex`/
+// `EdgeIndex` values, which are an implementation detail hidden behind
+// `node_map`. Serialize as a `NodeID`-keyed edge list instead, and rebuild the
+// graph (and `BiMap`) from it on load.
+impl Serialize for GameGraph {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let edges: Vec<(NodeID, NodeID, Requirement)> = self
+            .edge_indices()
+            .map(|e| {
+                let (a, b) = self.edge_endpoints(e).unwrap();
+                (a, b, self.requirement(e).unwrap())
+            })
+            .collect();
+        edges.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameGraph {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let edges = Vec::<(NodeID, NodeID, Requirement)>::deserialize(deserializer)?;
+        Ok(GameGraph::from_edges(edges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> {3, 4} -> 5 -> 6, plus an 8 -> 9 component unreachable
+    // from 0. 2 is a choke point: both branches out of it rejoin at 5, so 5's
+    // immediate dominator is 2, not 3 or 4.
+    fn graph() -> GameGraph {
+        GameGraph::from_edges(vec![
+            (0, 1, Requirement::none()),
+            (1, 2, Requirement::none()),
+            (2, 3, Requirement::none()),
+            (2, 4, Requirement::none()),
+            (3, 5, Requirement::none()),
+            (4, 5, Requirement::none()),
+            (5, 6, Requirement::none()),
+            (8, 9, Requirement::none()),
+        ])
+    }
+
+    #[test]
+    fn test_immediate_dominator() {
+        let g = graph();
+        let dominators = g.dominators(0).unwrap();
+
+        assert_eq!(dominators.immediate_dominator(5), Some(2));
+        assert_eq!(dominators.immediate_dominator(6), Some(5));
+        assert_eq!(dominators.immediate_dominator(0), None);
+    }
+
+    #[test]
+    fn test_dominator_chain() {
+        let g = graph();
+        let dominators = g.dominators(0).unwrap();
+
+        assert_eq!(dominators.dominator_chain(6), vec![6, 5, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_dominators_unreachable() {
+        let g = graph();
+        let dominators = g.dominators(0).unwrap();
+
+        assert!(!dominators.is_reachable(8));
+        assert_eq!(dominators.dominator_chain(8), Vec::<NodeID>::new());
+    }
+}
@@ -0,0 +1,10 @@
+mod error;
+mod game_world;
+mod graph;
+
+pub use error::{Error, Result};
+pub use game_world::{build_game, GameWorld, OneWay, TwoWay};
+pub use graph::{GameGraph, Graph};
+
+pub type NodeID = u32;
+pub type Item = u32;